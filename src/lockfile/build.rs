@@ -0,0 +1,22 @@
+//! Top-level pipeline: resolve `cfg`, download its packages, and optionally
+//! emit supply-chain artifacts alongside them.
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::config::Config;
+
+use super::{download, resolve};
+
+/// Resolves `cfg`, downloads its packages into `dest_dir`, and, if
+/// `sbom_path` is given, writes a CycloneDX SBOM for the resulting lockfile.
+pub(crate) fn build(cfg: &Config, dest_dir: &Path, sbom_path: Option<&Path>) -> Result<()> {
+    let lockfile = resolve::resolve(cfg)?;
+    download::download(&lockfile, dest_dir)?;
+
+    if let Some(sbom_path) = sbom_path {
+        lockfile.write_sbom_to_file(sbom_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Downloads the packages recorded in a [`Lockfile`], verifying each one
+//! before it's accepted.
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use super::verify::GlobalKeyCache;
+use super::{Lockfile, Package};
+
+/// Downloads every package in `lockfile` into `dest_dir`.
+///
+/// Each package is checked with [`Lockfile::verify_package`] as soon as it
+/// lands on disk, so a tampered RPM, or an unsigned one from a repository
+/// with `gpgcheck` enabled, fails the download instead of being silently
+/// installed.
+pub(crate) fn download(lockfile: &Lockfile, dest_dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dest_dir)
+        .with_context(|| format!("failed to create {}", dest_dir.display()))?;
+
+    let global_keys = GlobalKeyCache::default();
+    lockfile
+        .iter_packages()
+        .map(|pkg| download_one(lockfile, pkg, dest_dir, &global_keys))
+        .collect()
+}
+
+fn download_one(
+    lockfile: &Lockfile,
+    pkg: &Package,
+    dest_dir: &Path,
+    global_keys: &GlobalKeyCache,
+) -> Result<PathBuf> {
+    let rpm_path = fetch_rpm(pkg, dest_dir)
+        .with_context(|| format!("failed to download {}", pkg.name))?;
+
+    lockfile.verify_package(pkg, &rpm_path, global_keys)?;
+
+    Ok(rpm_path)
+}
+
+/// Fetches the RPM for `pkg` from its repository into `dest_dir` via the
+/// dnf download helper script, returning the path it was written to.
+fn fetch_rpm(pkg: &Package, dest_dir: &Path) -> Result<PathBuf> {
+    let rpm_path = dest_dir.join(format!("{}-{}.rpm", pkg.name, pkg.evr));
+
+    let status = Command::new("rpmoci-dnf-download")
+        .arg(&pkg.name)
+        .arg(&pkg.evr)
+        .arg(&pkg.repoid)
+        .arg(&rpm_path)
+        .status()
+        .context("failed to run dnf download script")?;
+
+    if !status.success() {
+        bail!("dnf download script exited with {status}");
+    }
+
+    Ok(rpm_path)
+}
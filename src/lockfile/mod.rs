@@ -14,12 +14,12 @@
 //!
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <https://www.gnu.org/licenses/>.
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt;
 use std::io::Write;
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
 use crate::write;
@@ -28,18 +28,58 @@ use crate::{NAME, config::Config};
 mod build;
 mod download;
 mod resolve;
+mod sbom;
+mod verify;
+mod version;
+
+pub use sbom::CycloneDxBom;
+pub use version::LockfileVersion;
 
 /// Represents an rpmoci lockfile
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct Lockfile {
+    version: LockfileVersion,
     pkg_specs: Vec<String>,
     packages: BTreeSet<Package>,
-    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
     local_packages: BTreeSet<LocalPackage>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    repo_gpg_config: BTreeMap<String, RepoKeyInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    global_key_specs: Vec<url::Url>,
+    /// For each entry of `pkg_specs`, the set of top-level resolved package
+    /// names that directly satisfied it.
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    specifiers: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Deserialization target for a [`Lockfile`] before schema migrations are
+/// applied. Unlike `Lockfile` itself, every field here must tolerate being
+/// absent, since it may be read from a lockfile written by an older rpmoci.
+#[derive(Debug, Deserialize)]
+struct RawLockfile {
+    #[serde(default)]
+    version: LockfileVersion,
+    pkg_specs: Vec<String>,
+    packages: BTreeSet<Package>,
+    #[serde(default)]
+    local_packages: BTreeSet<LocalPackage>,
+    #[serde(default)]
     repo_gpg_config: BTreeMap<String, RepoKeyInfo>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
     global_key_specs: Vec<url::Url>,
+    #[serde(default)]
+    specifiers: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl<'de> Deserialize<'de> for Lockfile {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw = RawLockfile::deserialize(deserializer)?;
+        version::migrate(raw).map_err(serde::de::Error::custom)
+    }
 }
 
 /// A package that the user has specified locally
@@ -66,6 +106,10 @@ struct DnfOutput {
     local_packages: Vec<LocalPackage>,
     /// Repository GPG configuration
     repo_gpg_config: BTreeMap<String, RepoKeyInfo>,
+    /// For each requested pkg_spec, the top-level resolved package names
+    /// that directly satisfied it
+    #[serde(default)]
+    specifiers: BTreeMap<String, BTreeSet<String>>,
 }
 
 /// GPG key configuration for a specified repository
@@ -93,6 +137,11 @@ pub struct Package {
     /// that requires this field, it should be made mandatory.
     #[serde(default)]
     pub arch: Option<String>,
+    /// The names of the packages this package directly depends on, as
+    /// produced by the dnf resolve step. Forms the edges of the dependency
+    /// graph used to answer `why`/`tree` queries.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
 }
 
 /// Checksum of RPM package
@@ -120,6 +169,43 @@ pub enum Algorithm {
     SHA512,
 }
 
+/// The result of comparing a [`Lockfile`] against a [`Config`], identifying
+/// precisely what (if anything) diverged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Compatibility {
+    /// The lockfile matches the configuration.
+    Compatible,
+    /// The configured `packages` no longer match the `pkg_specs` the
+    /// lockfile was resolved from.
+    SpecsChanged {
+        /// Specs present in the configuration but not the lockfile
+        added: BTreeSet<String>,
+        /// Specs present in the lockfile but not the configuration
+        removed: BTreeSet<String>,
+    },
+    /// The configured `gpgkeys` no longer match the lockfile's.
+    GpgKeysChanged,
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compatibility::Compatible => write!(f, "lockfile is up to date"),
+            Compatibility::SpecsChanged { added, removed } => {
+                write!(f, "package specs changed:")?;
+                for spec in added {
+                    write!(f, " +{spec}")?;
+                }
+                for spec in removed {
+                    write!(f, " -{spec}")?;
+                }
+                Ok(())
+            }
+            Compatibility::GpgKeysChanged => write!(f, "configured gpgkeys changed"),
+        }
+    }
+}
+
 impl fmt::Display for Algorithm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -140,7 +226,31 @@ impl Lockfile {
     /// without them being present
     #[must_use]
     pub fn is_compatible_excluding_local_rpms(&self, cfg: &Config) -> bool {
-        self.pkg_specs == cfg.contents.packages && self.global_key_specs == cfg.contents.gpgkeys
+        self.compatibility_with(cfg) == Compatibility::Compatible
+    }
+
+    /// Same check as [`Lockfile::is_compatible_excluding_local_rpms`], but
+    /// identifies which pkg_spec(s) changed rather than returning a bare
+    /// true/false. A gpgkeys change is reported as
+    /// [`Compatibility::GpgKeysChanged`] without a pkg_spec diff, and specs
+    /// that differ only in order give `SpecsChanged` with both sets empty.
+    #[must_use]
+    pub fn compatibility_with(&self, cfg: &Config) -> Compatibility {
+        if self.pkg_specs == cfg.contents.packages && self.global_key_specs == cfg.contents.gpgkeys
+        {
+            return Compatibility::Compatible;
+        }
+
+        if self.global_key_specs != cfg.contents.gpgkeys {
+            return Compatibility::GpgKeysChanged;
+        }
+
+        let old: BTreeSet<&String> = self.pkg_specs.iter().collect();
+        let new: BTreeSet<&String> = cfg.contents.packages.iter().collect();
+        Compatibility::SpecsChanged {
+            added: new.difference(&old).map(|s| (*s).clone()).collect(),
+            removed: old.difference(&new).map(|s| (*s).clone()).collect(),
+        }
     }
 
     /// Returns true if the lockfile is compatible with the
@@ -161,7 +271,21 @@ impl Lockfile {
     }
 
     /// Write the lockfile to a file on disk
+    ///
+    /// Refuses to write a lockfile whose `version` is newer than this build
+    /// of rpmoci understands: migrating it down through [`RawLockfile`]
+    /// would silently drop whatever fields that version added, re-emitting
+    /// a truncated file under the version it was read at.
     pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let LockfileVersion::Unknown(v) = self.version {
+            bail!(
+                "refusing to write lockfile: version {v} is newer than this build of {} understands (up to {}); upgrade {} instead",
+                NAME,
+                version::CURRENT_VERSION,
+                NAME,
+            );
+        }
+
         let mut lock = std::fs::File::create(path.as_ref())?;
         lock.write_all(
             format!(
@@ -197,18 +321,335 @@ impl Lockfile {
                     write::ok("Updating", format!("{name} {evr} -> {new_evr}"))?;
                 }
             } else {
-                write::ok("Removing", format!("{name} {evr}"))?;
+                let previous = previous.expect("old is non-empty only if previous is Some");
+                write::ok("Removing", Self::describe_change(previous, name, evr))?;
             }
         }
         for (name, evr) in new {
-            write::ok("Adding", format!("{name} {evr}"))?;
+            write::ok("Adding", Self::describe_change(self, name, evr))?;
         }
 
         Ok(())
     }
 
+    /// Formats a package name/evr for [`Lockfile::print_updates`], noting the
+    /// pkg_spec responsible for pulling it in when one can be identified.
+    fn describe_change(lockfile: &Lockfile, name: &str, evr: &str) -> String {
+        match lockfile.attributed_spec(name) {
+            Some(spec) => format!("{name} {evr} (required by {spec})"),
+            None => format!("{name} {evr}"),
+        }
+    }
+
+    /// Returns the pkg_spec (if any) whose resolution directly produced `name`.
+    fn spec_for(&self, name: &str) -> Option<&str> {
+        self.specifiers
+            .iter()
+            .find(|(_, resolved)| resolved.contains(name))
+            .map(|(spec, _)| spec.as_str())
+    }
+
+    /// Returns the pkg_spec responsible for pulling `name` into the lockfile,
+    /// following the reverse dependency graph up to the nearest ancestor that
+    /// a spec directly resolved to, when `name` wasn't itself requested.
+    ///
+    /// `reverse_dependencies` returns ancestors in BFS order, so the first
+    /// match here is genuinely the nearest one, not just the first
+    /// alphabetically.
+    fn attributed_spec(&self, name: &str) -> Option<&str> {
+        if let Some(spec) = self.spec_for(name) {
+            return Some(spec);
+        }
+
+        self.reverse_dependencies(name)
+            .iter()
+            .find_map(|ancestor| self.spec_for(ancestor))
+    }
+
+    /// Returns the set of top-level resolved package names that directly
+    /// satisfied `spec`, if `spec` is one of the configured `pkg_specs`.
+    #[must_use]
+    pub fn specifiers_for(&self, spec: &str) -> Option<&BTreeSet<String>> {
+        self.specifiers.get(spec)
+    }
+
     /// Returns an iterator over the packages in the Lockfile
     pub fn iter_packages(&self) -> impl Iterator<Item = &Package> {
         self.packages.iter()
     }
+
+    /// Builds the reverse dependency graph: for each package name, the names
+    /// of the packages that directly depend on it.
+    fn reverse_edges(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut edges: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for pkg in &self.packages {
+            for dep in &pkg.dependencies {
+                edges.entry(dep.as_str()).or_default().insert(&pkg.name);
+            }
+        }
+        edges
+    }
+
+    /// Returns the names of all packages that transitively depend on `name`,
+    /// found by walking the reverse dependency graph back towards the
+    /// user-requested `pkg_specs`. Answers "why is this package here".
+    ///
+    /// Returned in BFS order (nearest ancestors first), not alphabetically,
+    /// since [`Lockfile::attributed_spec`] relies on the first match being
+    /// the nearest one.
+    #[must_use]
+    pub fn reverse_dependencies(&self, name: &str) -> Vec<String> {
+        let edges = self.reverse_edges();
+        let mut seen: BTreeSet<&str> = BTreeSet::new();
+        let mut order: Vec<&str> = Vec::new();
+        let mut queue: VecDeque<&str> = VecDeque::new();
+        queue.push_back(name);
+
+        while let Some(current) = queue.pop_front() {
+            if let Some(parents) = edges.get(current) {
+                for &parent in parents {
+                    if seen.insert(parent) {
+                        order.push(parent);
+                        queue.push_back(parent);
+                    }
+                }
+            }
+        }
+
+        order.into_iter().map(str::to_owned).collect()
+    }
+
+    /// Writes an indented dependency tree rooted at `name`, in the style of
+    /// `cargo tree`. A package whose subtree has already been printed once
+    /// (whether that's a genuine cycle or just another path through a
+    /// diamond-shaped dependency, e.g. several packages pulling in glibc) is
+    /// marked `(*)` rather than re-expanded, matching `cargo tree`'s default
+    /// of deduplicating across the whole tree instead of just the current
+    /// path.
+    pub fn print_tree(&self, name: &str, out: &mut impl Write) -> Result<()> {
+        let forward: BTreeMap<&str, &Package> =
+            self.packages.iter().map(|p| (p.name.as_str(), p)).collect();
+        let mut expanded = BTreeSet::new();
+        Self::print_tree_node(name, &forward, 0, &mut expanded, out)
+    }
+
+    fn print_tree_node(
+        name: &str,
+        forward: &BTreeMap<&str, &Package>,
+        depth: usize,
+        expanded: &mut BTreeSet<String>,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        let indent = "  ".repeat(depth);
+        if !expanded.insert(name.to_owned()) {
+            writeln!(out, "{indent}{name} (*)")?;
+            return Ok(());
+        }
+
+        writeln!(out, "{indent}{name}")?;
+
+        let Some(pkg) = forward.get(name) else {
+            return Ok(());
+        };
+
+        for dep in &pkg.dependencies {
+            Self::print_tree_node(dep, forward, depth + 1, expanded, out)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pkg(name: &str, deps: &[&str]) -> Package {
+        Package {
+            name: name.to_owned(),
+            evr: "1.0-1".to_owned(),
+            checksum: Checksum {
+                algorithm: Algorithm::SHA256,
+                checksum: "deadbeef".to_owned(),
+            },
+            repoid: "repo".to_owned(),
+            arch: None,
+            dependencies: deps.iter().map(|d| (*d).to_owned()).collect(),
+        }
+    }
+
+    fn lockfile(packages: Vec<Package>) -> Lockfile {
+        Lockfile {
+            version: LockfileVersion::default(),
+            pkg_specs: Vec::new(),
+            packages: packages.into_iter().collect(),
+            local_packages: BTreeSet::new(),
+            repo_gpg_config: BTreeMap::new(),
+            global_key_specs: Vec::new(),
+            specifiers: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn reverse_dependencies_walks_transitively() {
+        let lock = lockfile(vec![
+            pkg("app", &["lib-a"]),
+            pkg("lib-a", &["lib-b"]),
+            pkg("lib-b", &[]),
+        ]);
+
+        let parents = lock.reverse_dependencies("lib-b");
+        assert_eq!(parents, vec!["lib-a".to_owned(), "app".to_owned()]);
+    }
+
+    #[test]
+    fn reverse_dependencies_of_unreferenced_package_is_empty() {
+        let lock = lockfile(vec![pkg("standalone", &[])]);
+        assert!(lock.reverse_dependencies("standalone").is_empty());
+    }
+
+    fn lockfile_with_specifiers(
+        packages: Vec<Package>,
+        specifiers: BTreeMap<String, BTreeSet<String>>,
+    ) -> Lockfile {
+        let mut lock = lockfile(packages);
+        lock.specifiers = specifiers;
+        lock
+    }
+
+    #[test]
+    fn spec_for_finds_the_spec_that_directly_resolved_to_name() {
+        let lock = lockfile_with_specifiers(
+            vec![pkg("app", &[])],
+            BTreeMap::from([("app".to_owned(), BTreeSet::from(["app".to_owned()]))]),
+        );
+
+        assert_eq!(lock.spec_for("app"), Some("app"));
+        assert_eq!(lock.spec_for("other"), None);
+    }
+
+    #[test]
+    fn specifiers_for_returns_the_resolved_names_for_a_known_spec() {
+        let lock = lockfile_with_specifiers(
+            vec![pkg("app", &[])],
+            BTreeMap::from([("app".to_owned(), BTreeSet::from(["app".to_owned()]))]),
+        );
+
+        assert_eq!(
+            lock.specifiers_for("app"),
+            Some(&BTreeSet::from(["app".to_owned()]))
+        );
+        assert_eq!(lock.specifiers_for("unknown-spec"), None);
+    }
+
+    #[test]
+    fn attributed_spec_prefers_a_direct_specifier_over_an_ancestor() {
+        let lock = lockfile_with_specifiers(
+            vec![pkg("app", &["lib"]), pkg("lib", &[])],
+            BTreeMap::from([
+                ("app".to_owned(), BTreeSet::from(["app".to_owned()])),
+                ("lib".to_owned(), BTreeSet::from(["lib".to_owned()])),
+            ]),
+        );
+
+        assert_eq!(lock.attributed_spec("lib"), Some("lib"));
+    }
+
+    #[test]
+    fn attributed_spec_falls_back_to_the_nearest_ancestors_spec() {
+        // "shared" is pulled in transitively by two specs at different
+        // distances: it is a direct dependency of "near" (resolved by
+        // "z-near-spec"), while "far" (resolved by "a-far-spec") only reaches
+        // "shared" through "near". The nearest spec ("z-near-spec") must win,
+        // not whichever sorts first alphabetically.
+        let lock = lockfile_with_specifiers(
+            vec![
+                pkg("far", &["near"]),
+                pkg("near", &["shared"]),
+                pkg("shared", &[]),
+            ],
+            BTreeMap::from([
+                ("a-far-spec".to_owned(), BTreeSet::from(["far".to_owned()])),
+                ("z-near-spec".to_owned(), BTreeSet::from(["near".to_owned()])),
+            ]),
+        );
+
+        assert_eq!(lock.attributed_spec("shared"), Some("z-near-spec"));
+    }
+
+    #[test]
+    fn attributed_spec_is_none_when_nothing_resolved_to_an_ancestor() {
+        let lock = lockfile(vec![pkg("standalone", &[])]);
+        assert_eq!(lock.attributed_spec("standalone"), None);
+    }
+
+    #[test]
+    fn describe_change_includes_the_attributed_spec_when_known() {
+        let lock = lockfile_with_specifiers(
+            vec![pkg("lib", &[])],
+            BTreeMap::from([("lib".to_owned(), BTreeSet::from(["lib".to_owned()]))]),
+        );
+
+        assert_eq!(
+            Lockfile::describe_change(&lock, "lib", "1.0-1"),
+            "lib 1.0-1 (required by lib)"
+        );
+    }
+
+    #[test]
+    fn describe_change_omits_the_attribution_when_unknown() {
+        let lock = lockfile(vec![pkg("standalone", &[])]);
+
+        assert_eq!(
+            Lockfile::describe_change(&lock, "standalone", "1.0-1"),
+            "standalone 1.0-1"
+        );
+    }
+
+    #[test]
+    fn reverse_dependencies_returns_nearest_ancestors_first() {
+        // "shared" is a direct dependency of "near" (distance 1) and only
+        // reachable through "near" to get to "far" (distance 2). BFS order
+        // must list "near" before "far" regardless of alphabetical order.
+        let lock = lockfile(vec![
+            pkg("far", &["near"]),
+            pkg("near", &["shared"]),
+            pkg("shared", &[]),
+        ]);
+
+        let ancestors = lock.reverse_dependencies("shared");
+        assert_eq!(ancestors, vec!["near".to_owned(), "far".to_owned()]);
+    }
+
+    #[test]
+    fn print_tree_marks_cycles_instead_of_recursing_forever() {
+        let lock = lockfile(vec![pkg("a", &["b"]), pkg("b", &["a"])]);
+
+        let mut out = Vec::new();
+        lock.print_tree("a", &mut out).unwrap();
+        let rendered = String::from_utf8(out).unwrap();
+
+        assert_eq!(rendered, "a\n  b\n    a (*)\n");
+    }
+
+    #[test]
+    fn print_tree_dedupes_diamond_shaped_dependencies() {
+        // Both "left" and "right" depend on "shared"; a real dependency
+        // resolution like this shouldn't re-expand "shared" a second time.
+        let lock = lockfile(vec![
+            pkg("app", &["left", "right"]),
+            pkg("left", &["shared"]),
+            pkg("right", &["shared"]),
+            pkg("shared", &[]),
+        ]);
+
+        let mut out = Vec::new();
+        lock.print_tree("app", &mut out).unwrap();
+        let rendered = String::from_utf8(out).unwrap();
+
+        assert_eq!(
+            rendered,
+            "app\n  left\n    shared\n  right\n    shared (*)\n"
+        );
+    }
 }
@@ -0,0 +1,47 @@
+//! Resolves configured package specs into a [`Lockfile`] by invoking dnf.
+use std::collections::BTreeSet;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::Config;
+
+use super::{DnfOutput, Lockfile, LockfileVersion};
+
+/// Runs the dnf resolve step against `cfg` and builds a fresh [`Lockfile`].
+///
+/// The resolve script reports, alongside each resolved package, the names
+/// of the packages it depends on -- this becomes `Package::dependencies`,
+/// the forward edges the `why`/`tree` queries walk. It also reports, for
+/// each entry of `cfg.contents.packages`, the top-level package names that
+/// directly satisfied it -- this becomes `Lockfile::specifiers`.
+pub(crate) fn resolve(cfg: &Config) -> Result<Lockfile> {
+    let output = run_dnf_resolve(cfg)?;
+
+    Ok(Lockfile {
+        version: LockfileVersion::default(),
+        pkg_specs: cfg.contents.packages.clone(),
+        packages: output.packages.into_iter().collect::<BTreeSet<_>>(),
+        local_packages: output.local_packages.into_iter().collect(),
+        repo_gpg_config: output.repo_gpg_config,
+        global_key_specs: cfg.contents.gpgkeys.clone(),
+        specifiers: output.specifiers,
+    })
+}
+
+/// Invokes the dnf resolve helper script and parses its JSON output.
+fn run_dnf_resolve(cfg: &Config) -> Result<DnfOutput> {
+    let output = Command::new("rpmoci-dnf-resolve")
+        .args(&cfg.contents.packages)
+        .output()
+        .context("failed to run dnf resolve script")?;
+
+    if !output.status.success() {
+        bail!(
+            "dnf resolve script failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    serde_json::from_slice(&output.stdout).context("failed to parse dnf resolve output")
+}
@@ -0,0 +1,201 @@
+//! Generates a CycloneDX Software Bill of Materials from a [`Lockfile`].
+//!
+//! The lockfile already records everything an SBOM component needs per
+//! package (`name`, `evr`, `checksum`, `repoid`, `arch`), so this is a pure
+//! projection of [`Lockfile::iter_packages`] (plus `local_packages`) into
+//! the CycloneDX component schema; it performs no I/O of its own beyond
+//! [`Lockfile::write_sbom_to_file`].
+use std::io::Write as _;
+use std::path::Path;
+
+use anyhow::Result;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+
+use super::{Algorithm, Lockfile, Package};
+
+/// Characters a package-url path segment or qualifier value must not
+/// contain unescaped, per the package-url spec.
+const PURL_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b'/')
+    .add(b'@')
+    .add(b'?')
+    .add(b'#')
+    .add(b'%')
+    .add(b' ');
+
+/// The CycloneDX spec version this module emits.
+const CYCLONEDX_SPEC_VERSION: &str = "1.5";
+
+/// A CycloneDX BOM describing the packages recorded in a [`Lockfile`].
+#[derive(Debug, Serialize)]
+pub struct CycloneDxBom {
+    #[serde(rename = "bomFormat")]
+    bom_format: &'static str,
+    #[serde(rename = "specVersion")]
+    spec_version: &'static str,
+    version: u32,
+    components: Vec<Component>,
+}
+
+/// A single CycloneDX component, representing either a resolved remote
+/// package or a local RPM supplied by the user.
+#[derive(Debug, Serialize)]
+struct Component {
+    #[serde(rename = "type")]
+    component_type: &'static str,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<Hash>,
+}
+
+#[derive(Debug, Serialize)]
+struct Hash {
+    alg: &'static str,
+    content: String,
+}
+
+impl Component {
+    fn from_package(pkg: &Package) -> Self {
+        Component {
+            component_type: "library",
+            name: pkg.name.clone(),
+            version: Some(pkg.evr.clone()),
+            purl: Some(package_url(pkg)),
+            hashes: vec![Hash {
+                alg: cyclonedx_algorithm(&pkg.checksum.algorithm),
+                content: pkg.checksum.checksum.clone(),
+            }],
+        }
+    }
+
+    fn from_local_package(name: &str) -> Self {
+        Component {
+            component_type: "library",
+            name: name.to_owned(),
+            version: None,
+            purl: None,
+            hashes: Vec::new(),
+        }
+    }
+}
+
+/// Builds the `pkg:rpm/...` PackageURL identifying a resolved package.
+///
+/// RPM `evr` strings commonly carry a leading `epoch:` (e.g.
+/// `2:2.34-60.el9`), but the package-url `rpm` type requires the epoch as a
+/// separate `epoch=` qualifier rather than embedded in the version segment,
+/// so it's split out here.
+fn package_url(pkg: &Package) -> String {
+    let (epoch, version) = split_evr(&pkg.evr);
+    let arch = pkg.arch.as_deref().unwrap_or("noarch");
+
+    let mut qualifiers = vec![format!("arch={}", percent_encode(arch))];
+    if let Some(epoch) = epoch {
+        qualifiers.push(format!("epoch={}", percent_encode(epoch)));
+    }
+
+    format!(
+        "pkg:rpm/{}/{}@{}?{}",
+        percent_encode(&pkg.repoid),
+        percent_encode(&pkg.name),
+        percent_encode(version),
+        qualifiers.join("&"),
+    )
+}
+
+/// Splits an RPM `evr` into its optional epoch and the remaining
+/// version-release, e.g. `"2:2.34-60.el9"` -> `(Some("2"), "2.34-60.el9")`.
+fn split_evr(evr: &str) -> (Option<&str>, &str) {
+    match evr.split_once(':') {
+        Some((epoch, rest)) => (Some(epoch), rest),
+        None => (None, evr),
+    }
+}
+
+/// Percent-encodes a purl path segment or qualifier value.
+fn percent_encode(value: &str) -> String {
+    utf8_percent_encode(value, PURL_ENCODE_SET).to_string()
+}
+
+/// Maps an RPM [`Algorithm`] to the hash algorithm name CycloneDX expects.
+fn cyclonedx_algorithm(algorithm: &Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::MD5 => "MD5", //Devskim: ignore DS126858
+        Algorithm::SHA1 => "SHA-1", //Devskim: ignore DS126858
+        Algorithm::SHA256 => "SHA-256",
+        Algorithm::SHA384 => "SHA-384",
+        Algorithm::SHA512 => "SHA-512",
+    }
+}
+
+impl Lockfile {
+    /// Builds a CycloneDX BOM describing every package and local RPM
+    /// recorded in this lockfile.
+    #[must_use]
+    pub fn to_cyclonedx(&self) -> CycloneDxBom {
+        let mut components: Vec<Component> =
+            self.packages.iter().map(Component::from_package).collect();
+        components.extend(
+            self.local_packages
+                .iter()
+                .map(|pkg| Component::from_local_package(&pkg.name)),
+        );
+
+        CycloneDxBom {
+            bom_format: "CycloneDX",
+            spec_version: CYCLONEDX_SPEC_VERSION,
+            version: 1,
+            components,
+        }
+    }
+
+    /// Writes a CycloneDX SBOM for this lockfile to a file on disk.
+    pub fn write_sbom_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = std::fs::File::create(path.as_ref())?;
+        file.write_all(serde_json::to_string_pretty(&self.to_cyclonedx())?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Checksum;
+    use super::*;
+
+    fn pkg(name: &str, evr: &str, arch: Option<&str>) -> Package {
+        Package {
+            name: name.to_owned(),
+            evr: evr.to_owned(),
+            checksum: Checksum {
+                algorithm: Algorithm::SHA256,
+                checksum: "deadbeef".to_owned(),
+            },
+            repoid: "repo".to_owned(),
+            arch: arch.map(str::to_owned),
+            dependencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn package_url_splits_epoch_into_a_qualifier() {
+        let url = package_url(&pkg("glibc", "2:2.34-60.el9", Some("x86_64")));
+        assert_eq!(url, "pkg:rpm/repo/glibc@2.34-60.el9?arch=x86_64&epoch=2");
+    }
+
+    #[test]
+    fn package_url_without_epoch_omits_the_qualifier() {
+        let url = package_url(&pkg("bash", "5.1.8-6.el9", None));
+        assert_eq!(url, "pkg:rpm/repo/bash@5.1.8-6.el9?arch=noarch");
+    }
+
+    #[test]
+    fn package_url_percent_encodes_special_characters() {
+        let url = package_url(&pkg("my pkg", "1.0-1", Some("x86_64")));
+        assert_eq!(url, "pkg:rpm/repo/my%20pkg@1.0-1?arch=x86_64");
+    }
+}
@@ -0,0 +1,281 @@
+//! Verifies downloaded RPMs against the integrity and trust information
+//! recorded in the [`Lockfile`].
+//!
+//! A mismatched checksum, or (for a repo with `gpgcheck` enabled) a
+//! signature that doesn't validate against any of that repo's trusted keys,
+//! fails the download rather than silently installing a package nothing
+//! vouches for. Trusted keys come from two places: those embedded per-repo
+//! in `repo_gpg_config` via `RepoKeyInfo::keys`, and `global_key_specs`,
+//! which holds `gpgkeys` URLs from the configuration that are fetched
+//! (`file://` or `http(s)://`) and checked alongside them.
+use std::cell::RefCell;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use digest::Digest;
+use md5::Md5; //Devskim: ignore DS126858
+use sha1::Sha1; //Devskim: ignore DS126858
+use sha2::{Sha256, Sha384, Sha512};
+
+use super::{Algorithm, Lockfile, Package};
+
+/// Caches the keys fetched from a [`Lockfile`]'s `global_key_specs` URLs, so
+/// a download of many packages fetches each URL at most once instead of on
+/// every package's signature check.
+#[derive(Default)]
+pub(crate) struct GlobalKeyCache(RefCell<Option<Result<Vec<Vec<u8>>, String>>>);
+
+impl GlobalKeyCache {
+    /// Returns the fetched `global_key_specs` keys for `lockfile`, fetching
+    /// and caching them on first use.
+    fn get(&self, lockfile: &Lockfile) -> Result<Vec<Vec<u8>>> {
+        if let Some(cached) = self.0.borrow().as_ref() {
+            return cached.clone().map_err(|msg| anyhow::anyhow!(msg));
+        }
+
+        let fetched: Result<Vec<Vec<u8>>, String> = lockfile
+            .global_key_specs
+            .iter()
+            .map(|url| {
+                fetch_key_bytes(url)
+                    .map_err(|e| format!("failed to fetch GPG key from '{url}': {e}"))
+            })
+            .collect();
+
+        *self.0.borrow_mut() = Some(fetched.clone());
+        fetched.map_err(|msg| anyhow::anyhow!(msg))
+    }
+}
+
+impl Lockfile {
+    /// Verifies that the RPM at `rpm_path` matches the checksum recorded for
+    /// `pkg`, and, if `pkg`'s repository has `gpgcheck` enabled, that its GPG
+    /// signature validates against the keys embedded for that repository or,
+    /// failing those, the keys fetched from `global_key_specs`.
+    pub fn verify_package(
+        &self,
+        pkg: &Package,
+        rpm_path: &Path,
+        global_keys: &GlobalKeyCache,
+    ) -> Result<()> {
+        verify_checksum(pkg, rpm_path)
+            .with_context(|| format!("checksum verification failed for {}", pkg.name))?;
+
+        let gpgcheck = self
+            .repo_gpg_config
+            .get(&pkg.repoid)
+            .is_some_and(|repo| repo.gpgcheck);
+        if gpgcheck {
+            self.verify_signature(pkg, rpm_path, global_keys)
+                .with_context(|| format!("GPG signature verification failed for {}", pkg.name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Validates `rpm_path`'s GPG signature against the keys embedded for
+    /// `pkg`'s repository, falling back to `global_key_specs` keys only if
+    /// none of the embedded ones validate it. This way a transient failure
+    /// fetching a `global_key_specs` URL can't fail packages whose repo
+    /// already carries a working embedded key.
+    fn verify_signature(
+        &self,
+        pkg: &Package,
+        rpm_path: &Path,
+        global_keys: &GlobalKeyCache,
+    ) -> Result<()> {
+        let embedded_keys: Vec<Vec<u8>> = self
+            .repo_gpg_config
+            .get(&pkg.repoid)
+            .map(|repo| {
+                repo.keys
+                    .iter()
+                    .map(|key| key.clone().into_bytes())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if embedded_keys.is_empty() && self.global_key_specs.is_empty() {
+            bail!(
+                "repository '{}' has gpgcheck enabled but has no GPG keys embedded or configured \
+                 via global_key_specs to verify against",
+                pkg.repoid
+            );
+        }
+
+        let rpm_package = rpm::Package::open(rpm_path)
+            .with_context(|| format!("failed to read RPM at {}", rpm_path.display()))?;
+
+        if any_key_validates(&rpm_package, &embedded_keys)? {
+            return Ok(());
+        }
+
+        if !self.global_key_specs.is_empty() {
+            let fetched = global_keys.get(self)?;
+            if any_key_validates(&rpm_package, &fetched)? {
+                return Ok(());
+            }
+        }
+
+        bail!(
+            "no configured GPG key for repository '{}' validated the signature on {}",
+            pkg.repoid,
+            pkg.name
+        )
+    }
+}
+
+/// Returns whether any of `keys` validates `rpm_package`'s signature.
+fn any_key_validates(rpm_package: &rpm::Package, keys: &[Vec<u8>]) -> Result<bool> {
+    for key in keys {
+        let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(key)
+            .context("failed to parse configured GPG key")?;
+        if rpm_package.verify_signature(&verifier).is_ok() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Fetches the raw bytes of a GPG key referenced by a `global_key_specs`
+/// URL, supporting the `file://` and `http(s)://` schemes DNF's own
+/// `gpgkey=` config directive accepts.
+fn fetch_key_bytes(url: &url::Url) -> Result<Vec<u8>> {
+    match url.scheme() {
+        "file" => {
+            let path = url
+                .to_file_path()
+                .map_err(|()| anyhow::anyhow!("'{url}' is not a valid file:// URL"))?;
+            std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+        }
+        "http" | "https" => {
+            let response = ureq::get(url.as_str())
+                .call()
+                .with_context(|| format!("request to '{url}' failed"))?;
+            let mut bytes = Vec::new();
+            response
+                .into_reader()
+                .read_to_end(&mut bytes)
+                .with_context(|| format!("failed to read response body from '{url}'"))?;
+            Ok(bytes)
+        }
+        scheme => bail!("unsupported scheme '{scheme}' in GPG key URL '{url}'"),
+    }
+}
+
+/// Recomputes `pkg`'s checksum from the bytes on disk and compares it
+/// against the value recorded in the lockfile.
+fn verify_checksum(pkg: &Package, rpm_path: &Path) -> Result<()> {
+    let mut file = std::fs::File::open(rpm_path)
+        .with_context(|| format!("failed to open {}", rpm_path.display()))?;
+
+    let digest = match pkg.checksum.algorithm {
+        Algorithm::MD5 => hash_with(&mut file, Md5::new())?,
+        Algorithm::SHA1 => hash_with(&mut file, Sha1::new())?,
+        Algorithm::SHA256 => hash_with(&mut file, Sha256::new())?,
+        Algorithm::SHA384 => hash_with(&mut file, Sha384::new())?,
+        Algorithm::SHA512 => hash_with(&mut file, Sha512::new())?,
+    };
+
+    if digest != pkg.checksum.checksum {
+        bail!(
+            "checksum mismatch for {}: expected {} ({}), got {digest}",
+            pkg.name,
+            pkg.checksum.checksum,
+            pkg.checksum.algorithm,
+        );
+    }
+
+    Ok(())
+}
+
+/// Streams `file` through `hasher` and returns the lowercase hex digest.
+fn hash_with(file: &mut std::fs::File, mut hasher: impl Digest) -> Result<String> {
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::super::Checksum;
+    use super::*;
+
+    fn write_temp_file(contents: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "rpmoci-verify-test-{}-{:x}",
+            std::process::id(),
+            contents.len()
+        ));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(contents)
+            .unwrap();
+        path
+    }
+
+    fn pkg_with_checksum(algorithm: Algorithm, checksum: &str) -> Package {
+        Package {
+            name: "test-pkg".to_owned(),
+            evr: "1.0-1".to_owned(),
+            checksum: Checksum {
+                algorithm,
+                checksum: checksum.to_owned(),
+            },
+            repoid: "repo".to_owned(),
+            arch: None,
+            dependencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn verify_checksum_accepts_a_matching_digest() {
+        let path = write_temp_file(b"hello world");
+        let pkg = pkg_with_checksum(
+            Algorithm::SHA256,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+        );
+
+        assert!(verify_checksum(&pkg, &path).is_ok());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn fetch_key_bytes_reads_a_file_url() {
+        let path = write_temp_file(b"-----BEGIN PGP PUBLIC KEY BLOCK-----\n");
+        let url = url::Url::from_file_path(&path).unwrap();
+
+        assert_eq!(
+            fetch_key_bytes(&url).unwrap(),
+            b"-----BEGIN PGP PUBLIC KEY BLOCK-----\n"
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn fetch_key_bytes_rejects_an_unsupported_scheme() {
+        let url = url::Url::parse("ftp://example.com/key.asc").unwrap();
+        assert!(fetch_key_bytes(&url).is_err());
+    }
+
+    #[test]
+    fn verify_checksum_rejects_a_mismatched_digest() {
+        let path = write_temp_file(b"hello world");
+        let pkg = pkg_with_checksum(
+            Algorithm::SHA256,
+            "0000000000000000000000000000000000000000000000000000000000000",
+        );
+
+        assert!(verify_checksum(&pkg, &path).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
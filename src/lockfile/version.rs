@@ -0,0 +1,139 @@
+//! Lockfile schema versioning and migrations.
+//!
+//! The on-disk format carries an ordinal `version`; moving between ordinals
+//! is handled by a small ordered chain of upgrade steps instead of making
+//! every field since-forever an `Option`. A lockfile with no `version` at
+//! all predates this field and is treated as [`LockfileVersion::V1`].
+//!
+//! A `version` higher than [`CURRENT_VERSION`] is something this build of
+//! rpmoci doesn't know how to upgrade *or* downgrade, so it's kept as
+//! [`LockfileVersion::Unknown`] and [`Lockfile::write_to_file`] refuses to
+//! write it back out: we'd otherwise have to drop whatever fields that
+//! future version added (they aren't in [`RawLockfile`]) and re-emit a
+//! truncated file still claiming to be that version.
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{Lockfile, RawLockfile};
+
+/// The lockfile schema version written by this build of rpmoci.
+pub(super) const CURRENT_VERSION: u32 = 1;
+
+/// The schema version of a [`Lockfile`](super::Lockfile).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "u32", from = "u32")]
+pub enum LockfileVersion {
+    /// The original lockfile format.
+    V1,
+    /// A schema version newer than this build of rpmoci knows how to migrate.
+    ///
+    /// Round-tripping a lockfile at this version must leave it untouched:
+    /// we don't understand what it means, so we have no business changing it.
+    Unknown(u32),
+}
+
+impl LockfileVersion {
+    fn as_u32(self) -> u32 {
+        match self {
+            LockfileVersion::V1 => 1,
+            LockfileVersion::Unknown(v) => v,
+        }
+    }
+}
+
+impl Default for LockfileVersion {
+    fn default() -> Self {
+        LockfileVersion::V1
+    }
+}
+
+impl From<u32> for LockfileVersion {
+    fn from(v: u32) -> Self {
+        match v {
+            1 => LockfileVersion::V1,
+            other => LockfileVersion::Unknown(other),
+        }
+    }
+}
+
+impl From<LockfileVersion> for u32 {
+    fn from(v: LockfileVersion) -> Self {
+        v.as_u32()
+    }
+}
+
+/// Upgrade a freshly-deserialized [`RawLockfile`] to the current schema.
+///
+/// Migrations run in order from the version the lockfile was read at up to
+/// [`CURRENT_VERSION`]. A version we don't recognise (newer than anything
+/// this build knows about) is passed through unchanged instead of being
+/// forced onto [`CURRENT_VERSION`].
+///
+/// There is only one known version so far, so this is currently a no-op
+/// beyond stamping the version; later schema changes should add a variant to
+/// [`LockfileVersion`] and an upgrade step here rather than widening fields
+/// to `Option` to cope with both old and new lockfiles at once.
+pub(super) fn migrate(raw: RawLockfile) -> Result<Lockfile> {
+    let version = match raw.version {
+        LockfileVersion::V1 => LockfileVersion::V1,
+        unknown @ LockfileVersion::Unknown(v) if v > CURRENT_VERSION => unknown,
+        LockfileVersion::Unknown(_) => LockfileVersion::V1,
+    };
+
+    Ok(Lockfile {
+        version,
+        pkg_specs: raw.pkg_specs,
+        packages: raw.packages,
+        local_packages: raw.local_packages,
+        repo_gpg_config: raw.repo_gpg_config,
+        global_key_specs: raw.global_key_specs,
+        specifiers: raw.specifiers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_with_version(version: LockfileVersion) -> RawLockfile {
+        RawLockfile {
+            version,
+            pkg_specs: Vec::new(),
+            packages: Default::default(),
+            local_packages: Default::default(),
+            repo_gpg_config: Default::default(),
+            global_key_specs: Vec::new(),
+            specifiers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn absent_version_defaults_to_v1() {
+        assert_eq!(LockfileVersion::default(), LockfileVersion::V1);
+    }
+
+    #[test]
+    fn known_version_round_trips_through_u32() {
+        assert_eq!(LockfileVersion::from(1u32), LockfileVersion::V1);
+        assert_eq!(u32::from(LockfileVersion::V1), 1);
+    }
+
+    #[test]
+    fn version_newer_than_current_is_kept_as_unknown() {
+        let future = CURRENT_VERSION + 1;
+        assert_eq!(LockfileVersion::from(future), LockfileVersion::Unknown(future));
+    }
+
+    #[test]
+    fn migrate_preserves_a_future_unknown_version() {
+        let future = CURRENT_VERSION + 1;
+        let lockfile = migrate(raw_with_version(LockfileVersion::Unknown(future))).unwrap();
+        assert_eq!(lockfile.version, LockfileVersion::Unknown(future));
+    }
+
+    #[test]
+    fn migrate_normalizes_a_stray_version_below_current() {
+        let lockfile = migrate(raw_with_version(LockfileVersion::Unknown(0))).unwrap();
+        assert_eq!(lockfile.version, LockfileVersion::V1);
+    }
+}